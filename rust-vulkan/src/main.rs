@@ -1,31 +1,97 @@
 use winit::EventsLoop;
+use winit::Window;
 use winit::WindowBuilder;
 use winit::{Event, WindowEvent};
 use winit::dpi::LogicalSize;
-use vulkano::instance::{Instance, InstanceExtensions, ApplicationInfo, Version, layers_list, PhysicalDevice, QueueFamily};
+use vulkano::instance::{Instance, InstanceExtensions, ApplicationInfo, Version, layers_list, PhysicalDevice, PhysicalDeviceType};
 use std::sync::Arc;
-use vulkano::instance::debug::{DebugCallback, MessageTypes};
+use vulkano::instance::debug::{DebugCallback, MessageSeverity, MessageType};
 use vulkano::device::{Device, DeviceExtensions, Queue, Features};
+use vulkano::swapchain::{self, Surface, Swapchain, SurfaceTransform, PresentMode, CompositeAlpha, ColorSpace, AcquireError};
+use vulkano::format::Format;
+use vulkano::image::{ImageUsage, SwapchainImage};
+use vulkano::framebuffer::{RenderPassAbstract, FramebufferAbstract, Framebuffer, Subpass};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::vertex::BufferlessDefinition;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::sync::GpuFuture;
+use vulkano_win::VkSurfaceBuild;
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
 
+mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: "
+            #version 450
+
+            layout(location = 0) out vec3 frag_color;
+
+            vec2 positions[3] = vec2[](
+                vec2(0.0, -0.5),
+                vec2(0.5, 0.5),
+                vec2(-0.5, 0.5)
+            );
+
+            vec3 colors[3] = vec3[](
+                vec3(1.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+                vec3(0.0, 0.0, 1.0)
+            );
+
+            void main() {
+                gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+                frag_color = colors[gl_VertexIndex];
+            }
+        "
+    }
+}
+
+mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: "
+            #version 450
+
+            layout(location = 0) in vec3 frag_color;
+            layout(location = 0) out vec4 out_color;
+
+            void main() {
+                out_color = vec4(frag_color, 1.0);
+            }
+        "
+    }
+}
+
 // a rust struct is basically a Kotlin data class, or more generally a named Tuple
 #[allow(unused)]
 struct HelloTriangleApp {
     //vulkan
     instance: Arc<Instance>,
+    debug_message_severity: MessageSeverity,
+    debug_message_type: MessageType,
     debug_callback: Option<DebugCallback>,
     physical_device_index: usize, // can't store PhysicalDevice directly (lifetime issues)
     device: Arc<Device>,
     graphics_queue: Arc<Queue>,
+    present_queue: Arc<Queue>,
+    swap_chain: Arc<Swapchain<Window>>,
+    swap_chain_images: Vec<Arc<SwapchainImage<Window>>>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    graphics_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    swap_chain_framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    recreate_swap_chain: bool,
 
     //winit
     events_loop: EventsLoop,
+    surface: Arc<Surface<Window>>,
 }
 
 struct QueueFamilyIndices {
-    graphics_family: i32
+    graphics_family: i32,
+    present_family: i32,
 }
 
 impl QueueFamilyIndices {
@@ -33,51 +99,86 @@ impl QueueFamilyIndices {
 
     fn new() -> Self {
         Self {
-            graphics_family: Self::NOT_INITIALIZED
+            graphics_family: Self::NOT_INITIALIZED,
+            present_family: Self::NOT_INITIALIZED,
         }
     }
 
     fn is_complete(&self) -> bool {
-        return self.graphics_family != Self::NOT_INITIALIZED
+        return self.graphics_family != Self::NOT_INITIALIZED && self.present_family != Self::NOT_INITIALIZED
     }
 }
 
 //Vulkan standard validation layers init
-const VALIDATION_LAYERS: &[&str; 1] = &["VK_LAYER_LUNARG_standard_validation"];
+const VALIDATION_LAYERS: &[&str; 1] = &["VK_LAYER_KHRONOS_validation"];
 
 #[cfg(all(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = true;
 #[cfg(not(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = false;
 
+// default severities/types of debug-utils messages to print; callers can override
+// these via HelloTriangleApp::init_with_debug_options
+const DEFAULT_DEBUG_MESSAGE_SEVERITY: MessageSeverity = MessageSeverity {
+    error: true,
+    warning: true,
+    information: false,
+    verbose: true,
+};
+const DEFAULT_DEBUG_MESSAGE_TYPE: MessageType = MessageType {
+    general: true,
+    validation: true,
+    performance: true,
+};
+
 // associated functions on the struct
 impl HelloTriangleApp {
     //capital Self = type, HelloTriangleApp in this case
     fn init() -> Self {
+        Self::init_with_debug_options(DEFAULT_DEBUG_MESSAGE_SEVERITY, DEFAULT_DEBUG_MESSAGE_TYPE)
+    }
+
+    // lets a caller pick which debug-utils message severities/types to print,
+    // instead of being stuck with DEFAULT_DEBUG_MESSAGE_SEVERITY/DEFAULT_DEBUG_MESSAGE_TYPE
+    fn init_with_debug_options(debug_message_severity: MessageSeverity, debug_message_type: MessageType) -> Self {
         let instance = Self::init_instance();
-        let debug_callback = Self::setup_debug_callback(&instance);
-        let physical_device_index = Self::get_physical_device_index(&instance);
-        let (device, graphics_queue) = Self::create_logical_device(&instance, physical_device_index);
-        let events_loop = Self::init_window();
+        let debug_callback = Self::setup_debug_callback(&instance, debug_message_severity, debug_message_type);
+        let (events_loop, surface) = Self::init_window(&instance);
+        let physical_device_index = Self::get_physical_device_index(&instance, &surface);
+        let (device, graphics_queue, present_queue) = Self::create_logical_device(&instance, &surface, physical_device_index);
+        let (swap_chain, swap_chain_images) = Self::create_swap_chain(&instance, &surface, physical_device_index, &device, &graphics_queue, &present_queue);
+        let render_pass = Self::create_render_pass(&device, swap_chain.format());
+        let graphics_pipeline = Self::create_graphics_pipeline(&device, swap_chain.dimensions(), &render_pass);
+        let swap_chain_framebuffers = Self::create_framebuffers(&swap_chain_images, &render_pass);
 
         Self {
             instance,
+            debug_message_severity,
+            debug_message_type,
             debug_callback,
             physical_device_index,
             device,
             graphics_queue,
-            events_loop
+            present_queue,
+            swap_chain,
+            swap_chain_images,
+            render_pass,
+            graphics_pipeline,
+            swap_chain_framebuffers,
+            recreate_swap_chain: false,
+            events_loop,
+            surface,
         }
     }
 
-    fn init_window() -> EventsLoop {
-        let event_loop = EventsLoop::new();
-        let _window_builder = WindowBuilder::new()
+    fn init_window(instance: &Arc<Instance>) -> (EventsLoop, Arc<Surface<Window>>) {
+        let events_loop = EventsLoop::new();
+        let surface = WindowBuilder::new()
             .with_title("Vulkan")
             .with_dimensions(LogicalSize::new(f64::from(WIDTH), f64::from(HEIGHT)))
-            .build(&event_loop)
-            .unwrap();
-        return event_loop;
+            .build_vk_surface(&events_loop, instance.clone())
+            .expect("failed to create window surface");
+        return (events_loop, surface);
     }
 
     fn init_instance() -> Arc<Instance> {
@@ -113,49 +214,76 @@ impl HelloTriangleApp {
     fn get_required_extensions() -> InstanceExtensions {
         let mut required_extensions = vulkano_win::required_extensions();
         if ENABLE_VALIDATION_LAYERS {
-            // TODO!: this should be ext_debug_utils (_report is deprecated), but that doesn't exist yet in vulkano
-            required_extensions.ext_debug_report = true;
+            required_extensions.ext_debug_utils = true;
         }
 
         return required_extensions;
     }
 
-    fn setup_debug_callback(instance: &Arc<Instance>) -> Option<DebugCallback> {
+    fn setup_debug_callback(instance: &Arc<Instance>, message_severity: MessageSeverity, message_type: MessageType) -> Option<DebugCallback> {
         if !ENABLE_VALIDATION_LAYERS {
             return None
         }
 
-        let msg_types = MessageTypes {
-            error: true,
-            warning: true,
-            performance_warning: true,
-            information: false,
-            debug: true,
-        };
-
-        let callback = DebugCallback::new(&instance, msg_types, |msg| {
-            println!("validation layer: {:?}", msg.description);
+        let callback = DebugCallback::new(&instance, message_severity, message_type, |msg| {
+            let severity = if msg.severity.error {
+                "error"
+            } else if msg.severity.warning {
+                "warning"
+            } else if msg.severity.information {
+                "information"
+            } else if msg.severity.verbose {
+                "verbose"
+            } else {
+                "unknown"
+            };
+
+            let ty = if msg.ty.general {
+                "general"
+            } else if msg.ty.validation {
+                "validation"
+            } else if msg.ty.performance {
+                "performance"
+            } else {
+                "unknown"
+            };
+
+            println!("validation layer [{}/{}]: {}", severity, ty, msg.description);
         }).ok();
 
         return callback;
     }
 
-    fn get_physical_device_index(instance: &Arc<Instance>) -> usize {
+    fn get_physical_device_index(instance: &Arc<Instance>, surface: &Arc<Surface<Window>>) -> usize {
         let physical_device = PhysicalDevice::enumerate(&instance)
-            .find(|device| Self::is_physical_device_suitable(device))
+            .map(|device| (device, Self::rate_physical_device(&device, surface)))
+            .filter(|(_, score)| *score > 0)
+            .max_by_key(|(_, score)| *score)
+            .map(|(device, _)| device)
             .expect("failed to find a suitable GPU!");
 
         println!("Using device: {} (type: {:?})", physical_device.name(), physical_device.ty());
         return physical_device.index();
     }
 
-    fn is_physical_device_suitable(device: &PhysicalDevice) -> bool {
-        let indices = Self::find_queue_families(device);
-        return indices.is_complete();
+    fn rate_physical_device(device: &PhysicalDevice, surface: &Arc<Surface<Window>>) -> i32 {
+        let indices = Self::find_queue_families(device, surface);
+        let extensions_supported = DeviceExtensions::supported_by_device(*device).khr_swapchain;
+        if !indices.is_complete() || !extensions_supported {
+            return 0;
+        }
+
+        let mut score = 0;
+        if device.ty() == PhysicalDeviceType::DiscreteGpu {
+            score += 1000;
+        }
+        score += device.limits().max_image_dimension_2d() as i32;
+
+        return score;
     }
 
 
-    fn find_queue_families(device: &PhysicalDevice) -> QueueFamilyIndices {
+    fn find_queue_families(device: &PhysicalDevice, surface: &Arc<Surface<Window>>) -> QueueFamilyIndices {
         let mut indices = QueueFamilyIndices::new();
 
         for (i, queue_family) in device.queue_families().enumerate() {
@@ -163,6 +291,10 @@ impl HelloTriangleApp {
                 indices.graphics_family = i as i32;
             }
 
+            if surface.is_supported(queue_family).unwrap_or(false) {
+                indices.present_family = i as i32;
+            }
+
             if indices.is_complete() {
                 break;
             }
@@ -171,31 +303,256 @@ impl HelloTriangleApp {
         return indices;
     }
 
-    //I'm not sure I understand why i have to explicitly define the lifetime here?
-    fn get_graphics_family_from_physical_device<'a>(physical_device: &'a PhysicalDevice) -> QueueFamily<'a> {
-        let indices = Self::find_queue_families(&physical_device);
-        let queue_family = physical_device.queue_families().nth(indices.graphics_family as usize).unwrap();
-        return queue_family
-    }
-
-    fn create_logical_device(instance: &Arc<Instance>, physical_device_index: usize) -> (Arc<Device>, Arc<Queue>) {
+    fn create_logical_device(instance: &Arc<Instance>, surface: &Arc<Surface<Window>>, physical_device_index: usize) -> (Arc<Device>, Arc<Queue>, Arc<Queue>) {
         let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
-        let graphics_family = Self::get_graphics_family_from_physical_device(&physical_device);
+        let indices = Self::find_queue_families(&physical_device, surface);
+
+        // request the graphics family, plus the present family only when it is a different
+        // one, so no family is listed twice; which returned queue is graphics and which
+        // is present is decided below by matching family ids, not by position in this list.
+        let mut unique_queue_families = vec![indices.graphics_family];
+        if indices.present_family != indices.graphics_family {
+            unique_queue_families.push(indices.present_family);
+        }
 
         let features = Features::none();
-        let extensions = DeviceExtensions::none();
+        let extensions = Self::device_extensions();
 
+        let queue_priority = 1.0;
         //priorities, list of pairs, is option some iterable? I don't get how this would work otherwise
         //actually, the code is looking for anything that can implement IntoIterator<Item = (QueueFamily<'a>, f32)>
         //so, option works
-        let queue_families = Some((graphics_family, 1.0));
+        let queue_families = unique_queue_families.iter().map(|&i| {
+            (physical_device.queue_families().nth(i as usize).unwrap(), queue_priority)
+        });
 
-        let (device, mut queues_iter) = Device::new(physical_device, &features, &extensions, queue_families)
+        let (device, queues_iter) = Device::new(physical_device, &features, &extensions, queue_families)
             .expect("Couldn't build logical device!");
 
-        //only 1 queue for now
-        let queues = queues_iter.next().unwrap();
-        return (device, queues)
+        // match each returned queue back to its family instead of assuming positional order
+        let queues: Vec<Arc<Queue>> = queues_iter.collect();
+        let find_queue = |family: i32| {
+            queues.iter()
+                .find(|queue| queue.family().id() == family as u32)
+                .expect("logical device did not return a queue for the requested family")
+                .clone()
+        };
+
+        let graphics_queue = find_queue(indices.graphics_family);
+        let present_queue = find_queue(indices.present_family);
+        return (device, graphics_queue, present_queue)
+    }
+
+    fn device_extensions() -> DeviceExtensions {
+        DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        }
+    }
+
+    fn create_swap_chain(
+        instance: &Arc<Instance>,
+        surface: &Arc<Surface<Window>>,
+        physical_device_index: usize,
+        device: &Arc<Device>,
+        graphics_queue: &Arc<Queue>,
+        present_queue: &Arc<Queue>,
+    ) -> (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>) {
+        let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
+        let capabilities = surface.capabilities(physical_device)
+            .expect("failed to get surface capabilities");
+
+        let surface_format = Self::choose_swap_surface_format(&capabilities.supported_formats);
+        let present_mode = Self::choose_swap_present_mode(capabilities.present_modes);
+        let extent = Self::choose_swap_extent(&capabilities);
+
+        let mut image_count = capabilities.min_image_count + 1;
+        if let Some(max_image_count) = capabilities.max_image_count {
+            image_count = image_count.min(max_image_count);
+        }
+
+        let image_usage = ImageUsage {
+            color_attachment: true,
+            ..ImageUsage::none()
+        };
+
+        let indices = Self::find_queue_families(&physical_device, surface);
+        let sharing: vulkano::sync::SharingMode = if indices.graphics_family != indices.present_family {
+            vec![graphics_queue, present_queue].as_slice().into()
+        } else {
+            graphics_queue.into()
+        };
+
+        let (swap_chain, images) = Swapchain::new(
+            device.clone(),
+            surface.clone(),
+            image_count,
+            surface_format.0,
+            extent,
+            1,
+            image_usage,
+            sharing,
+            SurfaceTransform::Identity,
+            CompositeAlpha::Opaque,
+            present_mode,
+            true,
+            None,
+        ).expect("failed to create swap chain!");
+
+        return (swap_chain, images);
+    }
+
+    fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace) {
+        *available_formats.iter()
+            .find(|(format, color_space)| {
+                *format == Format::B8G8R8A8Srgb && *color_space == ColorSpace::SrgbNonLinear
+            })
+            .unwrap_or_else(|| &available_formats[0])
+    }
+
+    fn choose_swap_present_mode(available_present_modes: vulkano::swapchain::SupportedPresentModes) -> PresentMode {
+        if available_present_modes.mailbox {
+            PresentMode::Mailbox
+        } else {
+            PresentMode::Fifo
+        }
+    }
+
+    fn choose_swap_extent(capabilities: &vulkano::swapchain::Capabilities) -> [u32; 2] {
+        if let Some(current_extent) = capabilities.current_extent {
+            return current_extent;
+        }
+
+        let mut actual_extent = [WIDTH, HEIGHT];
+        actual_extent[0] = capabilities.min_image_extent[0].max(capabilities.max_image_extent[0].min(actual_extent[0]));
+        actual_extent[1] = capabilities.min_image_extent[1].max(capabilities.max_image_extent[1].min(actual_extent[1]));
+        return actual_extent;
+    }
+
+    fn create_render_pass(device: &Arc<Device>, color_format: Format) -> Arc<dyn RenderPassAbstract + Send + Sync> {
+        Arc::new(vulkano::single_pass_renderpass!(device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: color_format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {}
+            }
+        ).unwrap())
+    }
+
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        swap_chain_extent: [u32; 2],
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        let vert_shader_module = vs::Shader::load(device.clone())
+            .expect("failed to create vertex shader module!");
+        let frag_shader_module = fs::Shader::load(device.clone())
+            .expect("failed to create fragment shader module!");
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        Arc::new(GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition {})
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())
+            .unwrap())
+    }
+
+    fn create_framebuffers(
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        render_pass: &Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+        swap_chain_images.iter()
+            .map(|image| {
+                let framebuffer = Framebuffer::start(render_pass.clone())
+                    .add(image.clone()).unwrap()
+                    .build().unwrap();
+                Arc::new(framebuffer) as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn rebuild_swap_chain(&mut self) {
+        let capabilities = self.surface.capabilities(PhysicalDevice::from_index(&self.instance, self.physical_device_index).unwrap())
+            .expect("failed to get surface capabilities");
+        let extent = Self::choose_swap_extent(&capabilities);
+        if extent[0] == 0 || extent[1] == 0 {
+            return;
+        }
+
+        let (swap_chain, swap_chain_images) = self.swap_chain.recreate_with_dimensions(extent)
+            .expect("failed to recreate swap chain!");
+        self.swap_chain = swap_chain;
+        self.swap_chain_images = swap_chain_images;
+
+        self.render_pass = Self::create_render_pass(&self.device, self.swap_chain.format());
+        self.graphics_pipeline = Self::create_graphics_pipeline(&self.device, self.swap_chain.dimensions(), &self.render_pass);
+        self.swap_chain_framebuffers = Self::create_framebuffers(&self.swap_chain_images, &self.render_pass);
+
+        self.recreate_swap_chain = false;
+    }
+
+    fn draw_frame(&mut self) {
+        if self.recreate_swap_chain {
+            self.rebuild_swap_chain();
+            if self.recreate_swap_chain {
+                // extent was still zero (minimized window) - try again next frame
+                return;
+            }
+        }
+
+        let (image_index, acquire_future) = match swapchain::acquire_next_image(self.swap_chain.clone(), None) {
+            Ok(result) => result,
+            Err(AcquireError::OutOfDate) => {
+                self.recreate_swap_chain = true;
+                return;
+            }
+            Err(err) => panic!("{:?}", err),
+        };
+
+        let clear_values = vec![[0.0, 0.0, 0.0, 1.0].into()];
+
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(self.device.clone(), self.graphics_queue.family())
+            .unwrap()
+            .begin_render_pass(self.swap_chain_framebuffers[image_index].clone(), false, clear_values)
+            .unwrap()
+            .draw(self.graphics_pipeline.clone(), &DynamicState::none(), vulkano::pipeline::vertex::BufferlessVertices { vertices: 3, instances: 1 }, (), ())
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let future = acquire_future
+            .then_execute(self.graphics_queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(self.present_queue.clone(), self.swap_chain.clone(), image_index)
+            .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                future.wait(None).unwrap();
+            }
+            Err(vulkano::sync::FlushError::OutOfDate) => {
+                self.recreate_swap_chain = true;
+            }
+            Err(err) => panic!("{:?}", err),
+        }
     }
 
     //&mut self = self: &mut Self
@@ -203,14 +560,22 @@ impl HelloTriangleApp {
         //why is there a builtin infinite loop construct in rust?
         loop {
             let mut done = false;
+            let mut recreate_swap_chain = false;
             self.events_loop.poll_events( |event| {
-                if let Event::WindowEvent { event: WindowEvent::CloseRequested, .. } = event {
-                    done = true
+                match event {
+                    Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => done = true,
+                    Event::WindowEvent { event: WindowEvent::Resized(_), .. } => recreate_swap_chain = true,
+                    _ => {}
                 }
             });
             if done {
                 return;
             }
+            if recreate_swap_chain {
+                self.recreate_swap_chain = true;
+            }
+
+            self.draw_frame();
         }
     }
 }